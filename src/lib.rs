@@ -1,5 +1,12 @@
-use process::{Command, ExitStatus};
-use std::{io, path::Path, process, time::Duration};
+use process::{Child, Command, ExitStatus};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use wait_timeout::ChildExt;
 
@@ -31,25 +38,676 @@ impl BinaryKind {
     }
 }
 
-pub fn run_with_timeout(command: &mut Command, timeout: Duration) -> Result<ExitStatus, RunError> {
+pub fn run_with_timeout(
+    command: &mut Command,
+    timeout: Duration,
+    termination_policy: TerminationPolicy,
+    resource_limits: ResourceLimits,
+    observer: Option<&dyn ProcessObserver>,
+) -> Result<ExitStatus, RunError> {
+    apply_resource_limits(command, resource_limits);
+    let command_string = format!("{:?}", command);
+    let binary_kind = binary_kind(Path::new(command.get_program()));
+
     let mut child = command.spawn().map_err(|error| RunError::Io {
         context: IoErrorContext::Command {
-            command: format!("{:?}", command),
+            command: command_string.clone(),
         },
         error,
     })?;
+
+    // Constructed only after a successful spawn, so a failure to exec never reaches the observer
+    // as a spurious `on_timeout` call.
+    let mut guard =
+        observer.map(|observer| ObserverGuard::new(observer, command_string, binary_kind));
+
     match child
         .wait_timeout(timeout)
         .map_err(context(IoErrorContext::WaitWithTimeout))?
     {
         None => {
-            child.kill().map_err(context(IoErrorContext::KillProcess))?;
-            child
-                .wait()
-                .map_err(context(IoErrorContext::WaitForProcess))?;
+            let exit_status = kill_and_wait(&mut child, termination_policy)?;
+            Err(classify_exit_status(exit_status))
+        }
+        Some(exit_status) => {
+            if let Some(guard) = &mut guard {
+                guard.record_exit(exit_status);
+            }
+            if exit_status.success() {
+                Ok(exit_status)
+            } else {
+                Err(classify_exit_status(exit_status))
+            }
+        }
+    }
+}
+
+/// Hooks for observing process execution, e.g. to emit timing metrics and success/failure
+/// counters per [`BinaryKind`], without this crate depending on any particular metrics backend.
+///
+/// All methods have no-op default implementations, so callers only need to override the ones
+/// they care about.
+pub trait ProcessObserver {
+    /// Called right after the child has been spawned.
+    fn on_spawn(&self, _command: &str, _binary_kind: BinaryKind) {}
+
+    /// Called once the child has exited on its own, before the timeout elapsed.
+    fn on_exit(
+        &self,
+        _command: &str,
+        _binary_kind: BinaryKind,
+        _elapsed: Duration,
+        _exit_status: ExitStatus,
+    ) {
+    }
+
+    /// Called when the child had to be killed because it ran past the timeout, or when waiting for
+    /// it failed before an exit status could be recorded.
+    fn on_timeout(&self, _command: &str, _binary_kind: BinaryKind, _elapsed: Duration) {}
+}
+
+/// An RAII guard that calls the wrapped [`ProcessObserver`]'s `on_spawn` on construction and
+/// guarantees `on_exit` or `on_timeout` runs exactly once on drop, even if `run_with_timeout`
+/// returns early through an error.
+struct ObserverGuard<'a> {
+    observer: &'a dyn ProcessObserver,
+    command: String,
+    binary_kind: BinaryKind,
+    start: Instant,
+    exit_status: Option<ExitStatus>,
+}
+
+impl<'a> ObserverGuard<'a> {
+    fn new(observer: &'a dyn ProcessObserver, command: String, binary_kind: BinaryKind) -> Self {
+        observer.on_spawn(&command, binary_kind);
+        ObserverGuard {
+            observer,
+            command,
+            binary_kind,
+            start: Instant::now(),
+            exit_status: None,
+        }
+    }
+
+    fn record_exit(&mut self, exit_status: ExitStatus) {
+        self.exit_status = Some(exit_status);
+    }
+}
+
+impl Drop for ObserverGuard<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        match self.exit_status {
+            Some(exit_status) => {
+                self.observer
+                    .on_exit(&self.command, self.binary_kind, elapsed, exit_status)
+            }
+            None => self
+                .observer
+                .on_timeout(&self.command, self.binary_kind, elapsed),
+        }
+    }
+}
+
+/// Soft resource limits applied to a spawned child on Unix, via `setrlimit`, before it execs.
+///
+/// A field left as `None` leaves that resource's limit as inherited from the parent process. Has
+/// no effect on non-Unix targets.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResourceLimits {
+    max_address_space_bytes: Option<u64>,
+    max_cpu_seconds: Option<u64>,
+    max_open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Creates a [`ResourceLimits`] that sets no limits, leaving every resource's limit as
+    /// inherited from the parent process.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the child's virtual address space (`RLIMIT_AS`), in bytes.
+    pub fn max_address_space_bytes(mut self, bytes: u64) -> Self {
+        self.max_address_space_bytes = Some(bytes);
+        self
+    }
+
+    /// Caps the CPU time (`RLIMIT_CPU`) the child may use, in seconds.
+    pub fn max_cpu_seconds(mut self, seconds: u64) -> Self {
+        self.max_cpu_seconds = Some(seconds);
+        self
+    }
+
+    /// Caps the number of open file descriptors (`RLIMIT_NOFILE`) the child may hold.
+    pub fn max_open_files(mut self, count: u64) -> Self {
+        self.max_open_files = Some(count);
+        self
+    }
+}
+
+/// A command that can have a `pre_exec` hook installed, abstracting over `std::process::Command`
+/// and `tokio::process::Command` (which exposes its own inherent `pre_exec` rather than
+/// implementing [`std::os::unix::process::CommandExt`]).
+#[cfg(unix)]
+trait PreExecHook {
+    /// # Safety
+    /// See [`std::os::unix::process::CommandExt::pre_exec`].
+    unsafe fn install_pre_exec<F>(&mut self, hook: F)
+    where
+        F: FnMut() -> io::Result<()> + Send + Sync + 'static;
+}
+
+#[cfg(unix)]
+impl PreExecHook for Command {
+    unsafe fn install_pre_exec<F>(&mut self, hook: F)
+    where
+        F: FnMut() -> io::Result<()> + Send + Sync + 'static,
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: forwarded to the caller of `install_pre_exec`.
+        unsafe { self.pre_exec(hook) };
+    }
+}
+
+#[cfg(all(unix, feature = "tokio"))]
+impl PreExecHook for tokio::process::Command {
+    unsafe fn install_pre_exec<F>(&mut self, hook: F)
+    where
+        F: FnMut() -> io::Result<()> + Send + Sync + 'static,
+    {
+        // SAFETY: forwarded to the caller of `install_pre_exec`.
+        unsafe { self.pre_exec(hook) };
+    }
+}
+
+/// Installs a `pre_exec` hook that applies `limits` to the child before it execs. Generic over
+/// both `std::process::Command` and `tokio::process::Command` via [`PreExecHook`].
+#[cfg(unix)]
+fn apply_resource_limits<C: PreExecHook>(command: &mut C, limits: ResourceLimits) {
+    if limits.max_address_space_bytes.is_none()
+        && limits.max_cpu_seconds.is_none()
+        && limits.max_open_files.is_none()
+    {
+        return;
+    }
+
+    // SAFETY: the hook only calls `getrlimit`/`setrlimit`, which are plain syscalls and safe to
+    // call between `fork` and `exec`.
+    unsafe {
+        command.install_pre_exec(move || {
+            if let Some(bytes) = limits.max_address_space_bytes {
+                set_soft_limit(libc::RLIMIT_AS, bytes)?;
+            }
+            if let Some(seconds) = limits.max_cpu_seconds {
+                set_soft_limit(libc::RLIMIT_CPU, seconds)?;
+            }
+            if let Some(count) = limits.max_open_files {
+                set_soft_limit(libc::RLIMIT_NOFILE, count)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_resource_limits<C>(_command: &mut C, _limits: ResourceLimits) {}
+
+/// Sets the soft limit for `resource` to `soft_limit`, leaving the hard limit untouched.
+#[cfg(unix)]
+fn set_soft_limit(resource: libc::__rlimit_resource_t, soft_limit: u64) -> io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid, appropriately-sized out-parameter for `getrlimit`.
+    if unsafe { libc::getrlimit(resource, &mut limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    limit.rlim_cur = soft_limit as libc::rlim_t;
+    // SAFETY: `limit` was just populated by `getrlimit` above; only the soft limit is changed.
+    if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// How a child process that needs to be stopped is shut down: on Unix, `SIGTERM` then `SIGKILL`
+/// after `grace_period`; on non-Unix targets, killed directly and `grace_period` is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct TerminationPolicy {
+    grace_period: Duration,
+}
+
+impl TerminationPolicy {
+    /// Sends `SIGTERM` on Unix and waits up to `grace_period` for the child to exit before
+    /// escalating to `SIGKILL`.
+    pub fn graceful(grace_period: Duration) -> Self {
+        TerminationPolicy { grace_period }
+    }
+
+    /// Kills the child immediately (`SIGKILL` on Unix), without attempting a graceful shutdown.
+    pub fn immediate() -> Self {
+        TerminationPolicy {
+            grace_period: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for TerminationPolicy {
+    /// Sends `SIGTERM` and gives the child 5 seconds to exit before escalating to `SIGKILL`.
+    fn default() -> Self {
+        TerminationPolicy::graceful(Duration::from_secs(5))
+    }
+}
+
+/// Runs `command` with a timeout, streaming its stdout and stderr line-by-line through `handler`.
+///
+/// Every line read from either stream is passed to `handler`, which decides whether the line is
+/// forwarded to the parent process's corresponding stream, suppressed, replaced with substitute
+/// text, or whether the child should be terminated right away. A terminate request is shut down
+/// using `termination_policy`, the same way a timeout is, but unlike a timeout it is not an error:
+/// the child's exit status and every line read so far (before any replacement) are returned to
+/// the caller.
+///
+/// Unlike [`run_with_timeout`], a natural (non-timeout) exit is always `Ok`, even if the exit
+/// status indicates failure: callers need the collected `lines` alongside the status regardless
+/// of how the child exited, so classifying the status into a [`RunError`] here would just make
+/// them unwrap it again.
+pub fn run_with_output_actions(
+    command: &mut Command,
+    timeout: Duration,
+    termination_policy: TerminationPolicy,
+    mut handler: impl FnMut(&str) -> LineAction,
+) -> Result<(ExitStatus, Vec<String>), RunError> {
+    let start = Instant::now();
+    let mut child = command
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|error| RunError::Io {
+            context: IoErrorContext::Command {
+                command: format!("{:?}", command),
+            },
+            error,
+        })?;
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+
+    let (tx, rx) = mpsc::channel();
+    let stdout_thread = spawn_line_reader(stdout, OutputStream::Stdout, tx.clone());
+    let stderr_thread = spawn_line_reader(stderr, OutputStream::Stderr, tx);
+
+    let mut lines = Vec::new();
+    let mut open_streams = 2;
+    let mut terminated = false;
+
+    while open_streams > 0 {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        match rx.recv_timeout(remaining) {
+            Ok(Message::Line(stream, line)) => {
+                lines.push(line.clone());
+                match handler(&line) {
+                    LineAction::Forward => forward_line(stream, &line),
+                    LineAction::Suppress => {}
+                    LineAction::Replace(text) => forward_line(stream, &text),
+                    LineAction::Terminate => {
+                        terminated = true;
+                        break;
+                    }
+                }
+            }
+            Ok(Message::Eof) => open_streams -= 1,
+            Err(mpsc::RecvTimeoutError::Timeout) => break,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    // Dropping `rx` lets the reader threads notice the closed channel and exit once the child's
+    // pipes are closed, which happens as soon as the child is killed below.
+    drop(rx);
+    if terminated {
+        let exit_status = kill_and_wait(&mut child, termination_policy)?;
+        let _ = stdout_thread.join();
+        let _ = stderr_thread.join();
+        return Ok((exit_status, lines));
+    }
+
+    if open_streams > 0 {
+        kill_and_wait(&mut child, termination_policy)?;
+        let _ = stdout_thread.join();
+        let _ = stderr_thread.join();
+        return Err(RunError::TimedOut);
+    }
+
+    let _ = stdout_thread.join();
+    let _ = stderr_thread.join();
+    match child
+        .wait_timeout(timeout.saturating_sub(start.elapsed()))
+        .map_err(context(IoErrorContext::WaitWithTimeout))?
+    {
+        Some(exit_status) => Ok((exit_status, lines)),
+        None => {
+            // Both output streams closed, but the child itself hasn't exited yet (e.g. it closed
+            // its own stdout/stderr while continuing to run); still needs killing and reaping.
+            kill_and_wait(&mut child, termination_policy)?;
             Err(RunError::TimedOut)
         }
-        Some(exit_status) => Ok(exit_status),
+    }
+}
+
+/// Runs `command` with a timeout, capturing its stdout and stderr into in-memory buffers.
+///
+/// If `max_bytes` is set, each stream stops appending to its buffer once the cap is reached, but
+/// the pipe keeps being drained so the child never blocks trying to write to a full pipe buffer.
+/// Truncation (if any) is reported back in the returned [`Output`]. On timeout the child is shut
+/// down using `termination_policy`, the same way [`run_with_timeout`] does, and everything
+/// captured up to that point is still returned, via [`RunError::TimedOutCapturing`].
+///
+/// Unlike [`run_with_timeout`], a natural (non-timeout) exit is always `Ok`, even if the exit
+/// status indicates failure: `output.status` carries that information alongside the captured
+/// buffers, which callers need regardless of success, instead of having to unwrap a [`RunError`]
+/// to get the output back out.
+pub fn run_with_timeout_capturing(
+    command: &mut Command,
+    timeout: Duration,
+    termination_policy: TerminationPolicy,
+    max_bytes: Option<usize>,
+) -> Result<Output, RunError> {
+    let mut child = command
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .map_err(|error| RunError::Io {
+            context: IoErrorContext::Command {
+                command: format!("{:?}", command),
+            },
+            error,
+        })?;
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+    let stdout_thread = spawn_capturing_reader(stdout, max_bytes);
+    let stderr_thread = spawn_capturing_reader(stderr, max_bytes);
+
+    match child
+        .wait_timeout(timeout)
+        .map_err(context(IoErrorContext::WaitWithTimeout))?
+    {
+        None => {
+            let status = kill_and_wait(&mut child, termination_policy)?;
+            let (stdout, stdout_truncated) = join_capture(stdout_thread)?;
+            let (stderr, stderr_truncated) = join_capture(stderr_thread)?;
+            Err(RunError::TimedOutCapturing {
+                output: Box::new(Output {
+                    status,
+                    stdout,
+                    stderr,
+                    stdout_truncated,
+                    stderr_truncated,
+                }),
+            })
+        }
+        Some(status) => {
+            let (stdout, stdout_truncated) = join_capture(stdout_thread)?;
+            let (stderr, stderr_truncated) = join_capture(stderr_thread)?;
+            Ok(Output {
+                status,
+                stdout,
+                stderr,
+                stdout_truncated,
+                stderr_truncated,
+            })
+        }
+    }
+}
+
+/// Async counterpart of [`run_with_timeout`]. Shares the same [`TerminationPolicy`] escalation,
+/// [`ResourceLimits`] application, [`ProcessObserver`] hooks, exit classification and
+/// [`IoErrorContext`] variants as the sync version.
+#[cfg(feature = "tokio")]
+pub async fn run_with_timeout_async(
+    command: &mut tokio::process::Command,
+    timeout: Duration,
+    termination_policy: TerminationPolicy,
+    resource_limits: ResourceLimits,
+    observer: Option<&dyn ProcessObserver>,
+) -> Result<ExitStatus, RunError> {
+    apply_resource_limits(command, resource_limits);
+    let command_string = format!("{:?}", command);
+    let binary_kind = binary_kind(Path::new(command.as_std().get_program()));
+
+    let mut child = command.spawn().map_err(|error| RunError::Io {
+        context: IoErrorContext::Command {
+            command: command_string.clone(),
+        },
+        error,
+    })?;
+
+    // Constructed only after a successful spawn, so a failure to exec never reaches the observer
+    // as a spurious `on_timeout` call.
+    let mut guard =
+        observer.map(|observer| ObserverGuard::new(observer, command_string, binary_kind));
+
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(exit_status) => {
+            let exit_status = exit_status.map_err(context(IoErrorContext::WaitWithTimeout))?;
+            if let Some(guard) = &mut guard {
+                guard.record_exit(exit_status);
+            }
+            if exit_status.success() {
+                Ok(exit_status)
+            } else {
+                Err(classify_exit_status(exit_status))
+            }
+        }
+        Err(_elapsed) => {
+            let exit_status = kill_and_wait_async(&mut child, termination_policy).await?;
+            Err(classify_exit_status(exit_status))
+        }
+    }
+}
+
+/// Async counterpart of [`kill_and_wait`], using `tokio`'s process and timer APIs.
+#[cfg(feature = "tokio")]
+async fn kill_and_wait_async(
+    child: &mut tokio::process::Child,
+    termination_policy: TerminationPolicy,
+) -> Result<ExitStatus, RunError> {
+    #[cfg(unix)]
+    if !termination_policy.grace_period.is_zero() {
+        let terminated = match child.id() {
+            // SAFETY: the child hasn't been reaped yet, so its pid is still valid.
+            Some(pid) => (unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) }) == 0,
+            None => false,
+        };
+        if terminated {
+            if let Ok(wait_result) =
+                tokio::time::timeout(termination_policy.grace_period, child.wait()).await
+            {
+                return wait_result.map_err(context(IoErrorContext::WaitWithTimeout));
+            }
+        }
+    }
+
+    child
+        .kill()
+        .await
+        .map_err(context(IoErrorContext::KillProcess))?;
+    child
+        .wait()
+        .await
+        .map_err(context(IoErrorContext::WaitForProcess))
+}
+
+/// Captured output and exit status of a child process run via [`run_with_timeout_capturing`].
+#[derive(Debug)]
+pub struct Output {
+    /// The exit status of the child process.
+    pub status: ExitStatus,
+    /// The child's captured stdout, possibly truncated (see `stdout_truncated`).
+    pub stdout: Vec<u8>,
+    /// The child's captured stderr, possibly truncated (see `stderr_truncated`).
+    pub stderr: Vec<u8>,
+    /// Whether `stdout` was cut off because it exceeded the configured `max_bytes` cap.
+    pub stdout_truncated: bool,
+    /// Whether `stderr` was cut off because it exceeded the configured `max_bytes` cap.
+    pub stderr_truncated: bool,
+}
+
+/// Reads `reader` to completion on a dedicated thread, appending to a buffer capped at
+/// `max_bytes` (if any). The pipe is always drained fully, even past the cap, so the writing end
+/// never blocks on a full pipe buffer.
+fn spawn_capturing_reader<R>(
+    mut reader: R,
+    max_bytes: Option<usize>,
+) -> thread::JoinHandle<io::Result<(Vec<u8>, bool)>>
+where
+    R: io::Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let mut truncated = false;
+        let mut chunk = [0u8; 8192];
+        loop {
+            let read = reader.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            match max_bytes {
+                Some(max_bytes) => {
+                    let remaining_capacity = max_bytes.saturating_sub(buffer.len());
+                    let take = remaining_capacity.min(read);
+                    buffer.extend_from_slice(&chunk[..take]);
+                    if take < read {
+                        truncated = true;
+                    }
+                }
+                None => buffer.extend_from_slice(&chunk[..read]),
+            }
+        }
+        Ok((buffer, truncated))
+    })
+}
+
+/// Joins a capturing reader thread and maps its result into a [`RunError`].
+fn join_capture(
+    thread: thread::JoinHandle<io::Result<(Vec<u8>, bool)>>,
+) -> Result<(Vec<u8>, bool), RunError> {
+    thread
+        .join()
+        .expect("capturing reader thread panicked")
+        .map_err(context(IoErrorContext::CaptureOutput))
+}
+
+/// Action requested by a line handler passed to [`run_with_output_actions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineAction {
+    /// Forward the line unchanged to the parent's corresponding stream.
+    Forward,
+    /// Drop the line; it is still included in the returned output.
+    Suppress,
+    /// Forward substitute text instead of the original line.
+    Replace(String),
+    /// Kill the child process right away, following the timeout cleanup path.
+    Terminate,
+}
+
+/// Which of the child's output streams a line came from.
+#[derive(Debug, Copy, Clone)]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A line (or end-of-stream marker) read from one of the child's output streams.
+enum Message {
+    Line(OutputStream, String),
+    Eof,
+}
+
+/// Reads `reader` line-by-line on a dedicated thread, sending each line (and a final EOF marker)
+/// over `tx`. Running one thread per stream avoids the deadlock that reading both pipes
+/// sequentially on a single thread could cause.
+fn spawn_line_reader<R>(
+    reader: R,
+    stream: OutputStream,
+    tx: mpsc::Sender<Message>,
+) -> thread::JoinHandle<()>
+where
+    R: io::Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = line.trim_end_matches(['\n', '\r']).to_string();
+                    if tx.send(Message::Line(stream, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = tx.send(Message::Eof);
+    })
+}
+
+/// Writes `line` to the parent's stdout or stderr, matching which stream it came from.
+fn forward_line(stream: OutputStream, line: &str) {
+    match stream {
+        OutputStream::Stdout => {
+            let _ = writeln!(io::stdout(), "{line}");
+        }
+        OutputStream::Stderr => {
+            let _ = writeln!(io::stderr(), "{line}");
+        }
+    }
+}
+
+/// Kills `child` and waits for it to exit, used both for timeouts and handler-requested
+/// termination.
+fn kill_and_wait(
+    child: &mut Child,
+    termination_policy: TerminationPolicy,
+) -> Result<ExitStatus, RunError> {
+    #[cfg(unix)]
+    if !termination_policy.grace_period.is_zero() {
+        // SAFETY: the child hasn't been reaped yet, so its pid is still valid.
+        let terminated = unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) } == 0;
+        if terminated {
+            if let Some(exit_status) = child
+                .wait_timeout(termination_policy.grace_period)
+                .map_err(context(IoErrorContext::WaitWithTimeout))?
+            {
+                return Ok(exit_status);
+            }
+        }
+    }
+
+    child.kill().map_err(context(IoErrorContext::KillProcess))?;
+    child
+        .wait()
+        .map_err(context(IoErrorContext::WaitForProcess))
+}
+
+/// Turns a non-successful [`ExitStatus`] into a [`RunError`] that describes how the process
+/// actually died, instead of the generic [`RunError::TimedOut`]. Shared by every entry point that
+/// needs to report on a child that didn't exit successfully.
+fn classify_exit_status(exit_status: ExitStatus) -> RunError {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = exit_status.signal() {
+            return RunError::Signaled { signal };
+        }
+    }
+    match exit_status.code() {
+        Some(code) if code != 0 => RunError::NonZeroExit { code },
+        _ => RunError::TimedOut,
     }
 }
 
@@ -60,6 +718,14 @@ pub enum RunError {
     #[error("Command timed out")]
     TimedOut,
 
+    /// Command timed out while its output was being captured via
+    /// [`run_with_timeout_capturing`](crate::run_with_timeout_capturing)
+    #[error("Command timed out")]
+    TimedOutCapturing {
+        /// Everything captured from the child's stdout/stderr before it was killed.
+        output: Box<Output>,
+    },
+
     /// An I/O error occured
     #[error("I/O error: {context}")]
     Io {
@@ -69,6 +735,20 @@ pub enum RunError {
         #[source]
         error: io::Error,
     },
+
+    /// The process was killed by a signal
+    #[error("Process was terminated by signal {signal}")]
+    Signaled {
+        /// The signal that terminated the process.
+        signal: i32,
+    },
+
+    /// The process exited with a non-zero exit code
+    #[error("Process exited with non-zero exit code {code}")]
+    NonZeroExit {
+        /// The exit code the process returned.
+        code: i32,
+    },
 }
 
 /// An I/O error occured while trying to run the disk image.
@@ -92,9 +772,372 @@ pub enum IoErrorContext {
     /// Failed to wait for process after killing it after timeout
     #[error("Failed to wait for process after killing it after timeout")]
     WaitForProcess,
+
+    /// Failed to read captured output from the child process
+    #[error("Failed to read captured output from the child process")]
+    CaptureOutput,
 }
 
 /// Helper function for IO error construction
 fn context(context: IoErrorContext) -> impl FnOnce(io::Error) -> RunError {
     |error| RunError::Io { context, error }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminate_action_reaps_child_and_returns_lines() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo one; echo two; echo three; exec sleep 5");
+
+        let (status, lines) = run_with_output_actions(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            |line| {
+                if line == "two" {
+                    LineAction::Terminate
+                } else {
+                    LineAction::Forward
+                }
+            },
+        )
+        .expect("a handler-requested terminate is not an error");
+
+        assert!(!status.success());
+        assert_eq!(lines, vec!["one".to_string(), "two".to_string()]);
+    }
+
+    #[test]
+    fn output_streams_closing_early_still_kills_and_reaps_the_child() {
+        let pid_file = std::env::temp_dir().join(format!(
+            "runner-utils-test-pid-{}-{}",
+            std::process::id(),
+            "streams-closing-early"
+        ));
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "echo $$ > {}; exec 1>&-; exec 2>&-; exec sleep 20",
+            pid_file.display()
+        ));
+
+        let result = run_with_output_actions(
+            &mut command,
+            Duration::from_millis(300),
+            TerminationPolicy::immediate(),
+            |_line| LineAction::Forward,
+        );
+        assert!(matches!(result, Err(RunError::TimedOut)));
+
+        let pid: libc::pid_t = std::fs::read_to_string(&pid_file)
+            .expect("child should have written its pid before closing its streams")
+            .trim()
+            .parse()
+            .expect("pid file should contain a valid pid");
+        let _ = std::fs::remove_file(&pid_file);
+
+        // SAFETY: signal 0 only probes whether the process exists; it sends nothing.
+        let still_alive = unsafe { libc::kill(pid, 0) } == 0;
+        assert!(
+            !still_alive,
+            "child should have been killed, not left running"
+        );
+    }
+
+    #[test]
+    fn resource_limits_actually_constrain_the_child() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo hi");
+
+        let error = run_with_timeout(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new().max_open_files(1),
+            None,
+        )
+        .expect_err("a 1-file-descriptor limit should prevent the child from even starting");
+
+        match error {
+            RunError::NonZeroExit { code } => assert_ne!(code, 0),
+            other => panic!("expected RunError::NonZeroExit, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn capturing_truncates_and_still_drains_a_full_pipe() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("yes | head -c 100000");
+
+        let output = run_with_timeout_capturing(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            Some(10),
+        )
+        .expect("the child must not block on a full pipe just because the cap was reached");
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), 10);
+        assert!(output.stdout_truncated);
+    }
+
+    #[test]
+    fn capturing_returns_partial_output_on_timeout() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo partial; exec sleep 5");
+
+        let error = run_with_timeout_capturing(
+            &mut command,
+            Duration::from_millis(200),
+            TerminationPolicy::immediate(),
+            None,
+        )
+        .expect_err("the command should time out");
+
+        match error {
+            RunError::TimedOutCapturing { output } => {
+                assert_eq!(output.stdout, b"partial\n");
+            }
+            other => panic!("expected RunError::TimedOutCapturing, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn graceful_policy_escalates_to_sigkill_when_child_ignores_sigterm() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("trap '' TERM; exec sleep 5");
+
+        let start = Instant::now();
+        let error = run_with_timeout(
+            &mut command,
+            Duration::from_millis(200),
+            TerminationPolicy::graceful(Duration::from_millis(300)),
+            ResourceLimits::new(),
+            None,
+        )
+        .expect_err("the command should time out");
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        match error {
+            RunError::Signaled { signal } => assert_eq!(signal, libc::SIGKILL),
+            other => panic!("expected RunError::Signaled(SIGKILL), got {other:?}"),
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        on_spawn: std::sync::atomic::AtomicUsize,
+        on_exit: std::sync::atomic::AtomicUsize,
+        on_timeout: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ProcessObserver for RecordingObserver {
+        fn on_spawn(&self, _command: &str, _binary_kind: BinaryKind) {
+            self.on_spawn
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        fn on_exit(
+            &self,
+            _command: &str,
+            _binary_kind: BinaryKind,
+            _elapsed: Duration,
+            _exit_status: ExitStatus,
+        ) {
+            self.on_exit
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        fn on_timeout(&self, _command: &str, _binary_kind: BinaryKind, _elapsed: Duration) {
+            self.on_timeout
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn observer_fires_on_exit_exactly_once_on_success() {
+        let observer = RecordingObserver::default();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("true");
+
+        run_with_timeout(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new(),
+            Some(&observer),
+        )
+        .expect("the command should succeed");
+
+        assert_eq!(
+            observer.on_spawn.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer.on_exit.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer
+                .on_timeout
+                .load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+    }
+
+    #[test]
+    fn observer_fires_on_timeout_exactly_once_on_timeout() {
+        let observer = RecordingObserver::default();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("exec sleep 5");
+
+        run_with_timeout(
+            &mut command,
+            Duration::from_millis(200),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new(),
+            Some(&observer),
+        )
+        .expect_err("the command should time out");
+
+        assert_eq!(
+            observer.on_spawn.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer.on_exit.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+        assert_eq!(
+            observer
+                .on_timeout
+                .load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+    }
+
+    #[test]
+    fn observer_is_not_notified_when_spawn_fails() {
+        let observer = RecordingObserver::default();
+        let mut command = Command::new("/does/not/exist/runner-utils-test-binary");
+
+        let result = run_with_timeout(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new(),
+            Some(&observer),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            observer.on_spawn.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+        assert_eq!(
+            observer.on_exit.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+        assert_eq!(
+            observer
+                .on_timeout
+                .load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_graceful_policy_escalates_to_sigkill_when_child_ignores_sigterm() {
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c").arg("trap '' TERM; exec sleep 5");
+
+        let start = Instant::now();
+        let error = run_with_timeout_async(
+            &mut command,
+            Duration::from_millis(200),
+            TerminationPolicy::graceful(Duration::from_millis(300)),
+            ResourceLimits::new(),
+            None,
+        )
+        .await
+        .expect_err("the command should time out");
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        match error {
+            RunError::Signaled { signal } => assert_eq!(signal, libc::SIGKILL),
+            other => panic!("expected RunError::Signaled(SIGKILL), got {other:?}"),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_observer_fires_on_exit_exactly_once_on_success() {
+        let observer = RecordingObserver::default();
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c").arg("true");
+
+        run_with_timeout_async(
+            &mut command,
+            Duration::from_secs(5),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new(),
+            Some(&observer),
+        )
+        .await
+        .expect("the command should succeed");
+
+        assert_eq!(
+            observer.on_spawn.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer.on_exit.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer
+                .on_timeout
+                .load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_observer_fires_on_timeout_exactly_once_on_timeout() {
+        let observer = RecordingObserver::default();
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c").arg("exec sleep 5");
+
+        run_with_timeout_async(
+            &mut command,
+            Duration::from_millis(200),
+            TerminationPolicy::immediate(),
+            ResourceLimits::new(),
+            Some(&observer),
+        )
+        .await
+        .expect_err("the command should time out");
+
+        assert_eq!(
+            observer.on_spawn.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+        assert_eq!(
+            observer.on_exit.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+        assert_eq!(
+            observer
+                .on_timeout
+                .load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+    }
+}